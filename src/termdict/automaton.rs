@@ -0,0 +1,212 @@
+use fst::Automaton;
+
+/// A Levenshtein automaton, used to stream only the terms of a
+/// `TermDictionary` that lie within a bounded edit distance of a query.
+///
+/// The automaton is intersected with the FST during traversal, so terms
+/// whose prefix already exceeds the distance bound are never visited.
+///
+/// The state of the automaton is the set of `(query_position, edits_used)`
+/// pairs that are still reachable after consuming a given input prefix. For
+/// a query of length `n` and a maximum distance `d`, a state never holds
+/// more than `2 * d + 1` relevant positions.
+///
+/// `query_position` and `edits_used` are counted in UTF-8 **bytes**, not
+/// `char`s: the FST feeds this automaton one byte at a time, so a single
+/// substitution of a multi-byte character (e.g. accented Latin or CJK)
+/// costs as many edits as it has bytes, not one. Callers matching
+/// non-ASCII queries should size `max_distance` accordingly, or normalize
+/// to an ASCII-safe representation before building the automaton.
+pub struct Levenshtein {
+    query: Vec<u8>,
+    max_distance: u8,
+}
+
+impl Levenshtein {
+    /// Creates a new Levenshtein automaton matching terms within
+    /// `max_distance` edits of `query`, where both the query and the
+    /// distance are measured in UTF-8 bytes (see the struct-level docs).
+    pub fn new(query: &str, max_distance: u8) -> Levenshtein {
+        Levenshtein {
+            query: query.as_bytes().to_vec(),
+            max_distance,
+        }
+    }
+
+    /// Adds the positions reachable from `state` through deletions (i.e.
+    /// advancing the query without consuming an input byte) until no new
+    /// position is found.
+    fn epsilon_closure(&self, state: &mut LevenshteinState) {
+        let mut i = 0;
+        while i < state.positions.len() {
+            let (pos, edits) = state.positions[i];
+            if pos < self.query.len() && edits < self.max_distance {
+                push_position(&mut state.positions, pos + 1, edits + 1);
+            }
+            i += 1;
+        }
+    }
+}
+
+/// Push `(pos, edits)` into `positions`, keeping only the smallest number
+/// of edits for a given position.
+fn push_position(positions: &mut Vec<(usize, u8)>, pos: usize, edits: u8) {
+    for existing in positions.iter_mut() {
+        if existing.0 == pos {
+            if edits < existing.1 {
+                existing.1 = edits;
+            }
+            return;
+        }
+    }
+    positions.push((pos, edits));
+}
+
+/// The set of `(query_position, edits_used)` pairs reachable by the
+/// automaton for a given input prefix.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LevenshteinState {
+    positions: Vec<(usize, u8)>,
+    query_len: usize,
+}
+
+impl LevenshteinState {
+    /// Returns the edit distance of the term matched so far, i.e. the
+    /// smallest number of edits used among the positions that have
+    /// reached the end of the query.
+    ///
+    /// This is what a fuzzy query scorer should read off of
+    /// `TermStreamerWithState::state()` to rank closer matches higher.
+    /// Returns `None` if the query has not been fully matched yet (the
+    /// state does not correspond to a completed term).
+    pub fn distance(&self) -> Option<u8> {
+        self.positions
+            .iter()
+            .filter(|&&(pos, _)| pos == self.query_len)
+            .map(|&(_, edits)| edits)
+            .min()
+    }
+}
+
+impl Automaton for Levenshtein {
+    type State = LevenshteinState;
+
+    fn start(&self) -> LevenshteinState {
+        let mut state = LevenshteinState {
+            positions: vec![(0, 0)],
+            query_len: self.query.len(),
+        };
+        self.epsilon_closure(&mut state);
+        state
+    }
+
+    fn is_match(&self, state: &LevenshteinState) -> bool {
+        state
+            .positions
+            .iter()
+            .any(|&(pos, edits)| pos == self.query.len() && edits <= self.max_distance)
+    }
+
+    fn can_match(&self, state: &LevenshteinState) -> bool {
+        !state.positions.is_empty()
+    }
+
+    fn accept(&self, state: &LevenshteinState, byte: u8) -> LevenshteinState {
+        let mut next_positions = Vec::new();
+        for &(pos, edits) in &state.positions {
+            if edits >= self.max_distance && pos == self.query.len() {
+                continue;
+            }
+            // Match or substitution.
+            if pos < self.query.len() {
+                let substitution_cost = if self.query[pos] == byte { 0 } else { 1 };
+                let next_edits = edits + substitution_cost;
+                if next_edits <= self.max_distance {
+                    push_position(&mut next_positions, pos + 1, next_edits);
+                }
+            }
+            // Insertion: the input byte does not advance the query.
+            if edits + 1 <= self.max_distance {
+                push_position(&mut next_positions, pos, edits + 1);
+            }
+        }
+        let mut next_state = LevenshteinState {
+            positions: next_positions,
+            query_len: self.query.len(),
+        };
+        self.epsilon_closure(&mut next_state);
+        next_state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(automaton: &Levenshtein, input: &[u8]) -> LevenshteinState {
+        let mut state = automaton.start();
+        for &byte in input {
+            state = automaton.accept(&state, byte);
+        }
+        state
+    }
+
+    #[test]
+    fn test_exact_match() {
+        let automaton = Levenshtein::new("abc", 1);
+        let state = run(&automaton, b"abc");
+        assert!(automaton.is_match(&state));
+        assert_eq!(state.distance(), Some(0));
+    }
+
+    #[test]
+    fn test_substitution_within_distance() {
+        let automaton = Levenshtein::new("abc", 1);
+        let state = run(&automaton, b"abx");
+        assert!(automaton.is_match(&state));
+        assert_eq!(state.distance(), Some(1));
+    }
+
+    #[test]
+    fn test_insertion_within_distance() {
+        let automaton = Levenshtein::new("abc", 1);
+        // "aabc" inserts an extra 'a' in front of the query.
+        let state = run(&automaton, b"aabc");
+        assert!(automaton.is_match(&state));
+        assert_eq!(state.distance(), Some(1));
+    }
+
+    #[test]
+    fn test_deletion_within_distance() {
+        let automaton = Levenshtein::new("abc", 1);
+        // "ac" is "abc" with the 'b' deleted.
+        let state = run(&automaton, b"ac");
+        assert!(automaton.is_match(&state));
+        assert_eq!(state.distance(), Some(1));
+    }
+
+    #[test]
+    fn test_beyond_max_distance_does_not_match() {
+        let automaton = Levenshtein::new("abc", 1);
+        let state = run(&automaton, b"xyz");
+        assert!(!automaton.is_match(&state));
+    }
+
+    #[test]
+    fn test_beyond_max_distance_prunes_dead_states() {
+        let automaton = Levenshtein::new("abc", 1);
+        let state = run(&automaton, b"xyz");
+        // Every reachable position is already over budget, so the
+        // automaton can safely be pruned instead of consuming more input.
+        assert!(!automaton.can_match(&state));
+    }
+
+    #[test]
+    fn test_distance_is_none_before_full_match() {
+        // Matching the remaining "bc" would take 2 deletions, more than
+        // the budget of 1, so the query end is not yet reachable.
+        let automaton = Levenshtein::new("abc", 1);
+        let state = run(&automaton, b"a");
+        assert_eq!(state.distance(), None);
+    }
+}
@@ -0,0 +1,186 @@
+use super::{TermDictionary, TermStreamer};
+use postings::TermInfo;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// The ordinal of a segment within the set of segments being merged.
+pub type SegmentOrdinal = usize;
+
+struct HeapItem {
+    streamer_ord: SegmentOrdinal,
+    key: Vec<u8>,
+}
+
+impl Eq for HeapItem {}
+
+impl PartialEq for HeapItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Ord for HeapItem {
+    // `BinaryHeap` is a max-heap: reverse the key ordering so that the
+    // item with the smallest key is popped first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.cmp(&self.key)
+    }
+}
+
+impl PartialOrd for HeapItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Merges several `TermDictionary` streams in lockstep, yielding each
+/// distinct term exactly once along with the `(SegmentOrdinal, TermInfo)`
+/// of every segment that contains it.
+///
+/// This is the core primitive used to walk the global term space during
+/// segment merge, without materializing every dictionary in memory.
+pub struct TermMerger<'a> {
+    streamers: Vec<TermStreamer<'a>>,
+    heap: BinaryHeap<HeapItem>,
+    current_key: Vec<u8>,
+    current_segment_and_term_infos: Vec<(SegmentOrdinal, TermInfo)>,
+}
+
+impl<'a> TermMerger<'a> {
+    /// Creates a new `TermMerger` walking the given term dictionaries.
+    pub fn new(term_dictionaries: &[&'a TermDictionary]) -> TermMerger<'a> {
+        let streamers: Vec<TermStreamer<'a>> = term_dictionaries
+            .iter()
+            .map(|term_dictionary| term_dictionary.stream())
+            .collect();
+        let mut merger = TermMerger {
+            streamers,
+            heap: BinaryHeap::new(),
+            current_key: Vec::new(),
+            current_segment_and_term_infos: Vec::new(),
+        };
+        for streamer_ord in 0..merger.streamers.len() {
+            merger.advance_segment(streamer_ord);
+        }
+        merger
+    }
+
+    /// Advances the streamer of the given segment and, if it still has
+    /// terms left, pushes its current key onto the heap.
+    fn advance_segment(&mut self, streamer_ord: SegmentOrdinal) {
+        if self.streamers[streamer_ord].advance() {
+            let key = self.streamers[streamer_ord].key().to_vec();
+            self.heap.push(HeapItem { streamer_ord, key });
+        }
+    }
+
+    /// Advances to the next distinct term, across all segments.
+    ///
+    /// Returns `false` once every segment's stream is exhausted.
+    pub fn advance(&mut self) -> bool {
+        self.current_segment_and_term_infos.clear();
+        let first = match self.heap.pop() {
+            Some(head) => head,
+            None => return false,
+        };
+        self.current_key.clear();
+        self.current_key.extend_from_slice(&first.key);
+        self.current_segment_and_term_infos.push((
+            first.streamer_ord,
+            self.streamers[first.streamer_ord].value().clone(),
+        ));
+        self.advance_segment(first.streamer_ord);
+        while let Some(next_ord) = self.pop_if_matching_current_key() {
+            self.current_segment_and_term_infos
+                .push((next_ord, self.streamers[next_ord].value().clone()));
+            self.advance_segment(next_ord);
+        }
+        // Segments sharing a key are popped off the heap in an unspecified
+        // order (ties compare `Equal`), so restore ascending segment order
+        // here: merge consumers concatenate postings assuming increasing
+        // ordinals.
+        self.current_segment_and_term_infos
+            .sort_by_key(|&(streamer_ord, _)| streamer_ord);
+        true
+    }
+
+    fn pop_if_matching_current_key(&mut self) -> Option<SegmentOrdinal> {
+        let matches = self
+            .heap
+            .peek()
+            .map(|head| head.key == self.current_key)
+            .unwrap_or(false);
+        if matches {
+            self.heap.pop().map(|head| head.streamer_ord)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the term currently pointed at.
+    pub fn current_key(&self) -> &[u8] {
+        &self.current_key
+    }
+
+    /// Returns the list of `(SegmentOrdinal, TermInfo)` of the segments
+    /// that contain the term currently pointed at.
+    pub fn current_kvs(&self) -> &[(SegmentOrdinal, TermInfo)] {
+        &self.current_segment_and_term_infos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merges_keys_in_sorted_order_across_segments() {
+        let seg0 = TermDictionary::for_test(&[("aa", TermInfo::default()), ("cc", TermInfo::default())]);
+        let seg1 = TermDictionary::for_test(&[("bb", TermInfo::default())]);
+
+        let mut merger = TermMerger::new(&[&seg0, &seg1]);
+
+        let mut keys = Vec::new();
+        while merger.advance() {
+            keys.push(merger.current_key().to_vec());
+        }
+        assert_eq!(keys, vec![b"aa".to_vec(), b"bb".to_vec(), b"cc".to_vec()]);
+    }
+
+    #[test]
+    fn test_shared_term_yields_one_entry_per_segment_in_ascending_order() {
+        let seg0 = TermDictionary::for_test(&[("shared", TermInfo::default())]);
+        let seg1 = TermDictionary::for_test(&[("shared", TermInfo::default())]);
+        let seg2 = TermDictionary::for_test(&[("shared", TermInfo::default())]);
+
+        let mut merger = TermMerger::new(&[&seg0, &seg1, &seg2]);
+        assert!(merger.advance());
+        assert_eq!(merger.current_key(), b"shared");
+
+        let ordinals: Vec<SegmentOrdinal> = merger.current_kvs().iter().map(|&(ord, _)| ord).collect();
+        assert_eq!(ordinals, vec![0, 1, 2]);
+
+        assert!(!merger.advance());
+    }
+
+    #[test]
+    fn test_term_present_in_some_segments_only() {
+        let seg0 = TermDictionary::for_test(&[("only_in_0", TermInfo::default())]);
+        let seg1 = TermDictionary::for_test(&[("in_both", TermInfo::default())]);
+        let seg2 = TermDictionary::for_test(&[("in_both", TermInfo::default())]);
+
+        let mut merger = TermMerger::new(&[&seg0, &seg1, &seg2]);
+
+        assert!(merger.advance());
+        assert_eq!(merger.current_key(), b"in_both");
+        let ordinals: Vec<SegmentOrdinal> = merger.current_kvs().iter().map(|&(ord, _)| ord).collect();
+        assert_eq!(ordinals, vec![1, 2]);
+
+        assert!(merger.advance());
+        assert_eq!(merger.current_key(), b"only_in_0");
+        let ordinals: Vec<SegmentOrdinal> = merger.current_kvs().iter().map(|&(ord, _)| ord).collect();
+        assert_eq!(ordinals, vec![0]);
+
+        assert!(!merger.advance());
+    }
+}
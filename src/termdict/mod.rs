@@ -0,0 +1,68 @@
+use fst::automaton::AlwaysMatch;
+use fst::{Map, MapBuilder};
+use postings::TermInfo;
+
+mod automaton;
+mod merger;
+mod streamer;
+
+pub use self::automaton::{Levenshtein, LevenshteinState};
+pub use self::merger::{SegmentOrdinal, TermMerger};
+pub use self::streamer::{
+    TermStreamer, TermStreamerBuilder, TermStreamerIter, TermStreamerWithState,
+    TermStreamerWithStateBuilder,
+};
+
+/// Each term of a segment is assigned a `TermOrdinal`: its rank in the
+/// lexicographically sorted list of terms of that segment.
+pub type TermOrdinal = u64;
+
+/// `TermDictionary` is an immutable, sorted mapping from terms to
+/// `TermOrdinal`s, backed by an `fst::Map`, plus a side-table resolving
+/// each `TermOrdinal` to its `TermInfo`.
+pub struct TermDictionary {
+    fst_map: Map<Vec<u8>>,
+    term_infos: Vec<TermInfo>,
+}
+
+impl TermDictionary {
+    /// Returns the underlying `fst::Map`. Used by `TermStreamerBuilder`
+    /// to intersect an `fst::Automaton` with the term space.
+    pub(crate) fn fst_map(&self) -> &Map<Vec<u8>> {
+        &self.fst_map
+    }
+
+    /// Resolves a `TermOrdinal` to its `TermInfo`.
+    pub(crate) fn term_info_from_ord(&self, term_ord: TermOrdinal) -> TermInfo {
+        self.term_infos[term_ord as usize].clone()
+    }
+
+    /// Returns a `TermStreamerBuilder` over the whole dictionary, which
+    /// can be restricted with `.ge()`/`.gt()`/`.le()`/`.lt()`, or swapped
+    /// to a different automaton with `.automaton()`/`.regex()`/`.fuzzy()`.
+    pub fn range(&self) -> TermStreamerBuilder<'_> {
+        TermStreamerBuilder::new(self, self.fst_map.search(AlwaysMatch))
+    }
+
+    /// Returns a `TermStreamer` over the whole dictionary.
+    pub fn stream(&self) -> TermStreamer<'_> {
+        self.range().into_stream()
+    }
+
+    /// Builds a `TermDictionary` from an already-sorted, deduplicated
+    /// list of `(term, TermInfo)` pairs. Used by tests to exercise
+    /// `TermStreamer`/`TermMerger` without a full segment on disk.
+    #[cfg(test)]
+    pub(crate) fn for_test(terms: &[(&str, TermInfo)]) -> TermDictionary {
+        let mut builder = MapBuilder::memory();
+        for (ord, &(term, _)) in terms.iter().enumerate() {
+            builder
+                .insert(term.as_bytes(), ord as u64)
+                .expect("terms passed to TermDictionary::for_test must be sorted and unique");
+        }
+        let fst_bytes = builder.into_inner().expect("failed to serialize test FST");
+        let fst_map = Map::new(fst_bytes).expect("failed to load test FST");
+        let term_infos = terms.iter().map(|&(_, ref term_info)| term_info.clone()).collect();
+        TermDictionary { fst_map, term_infos }
+    }
+}
@@ -1,72 +1,189 @@
+use super::automaton::Levenshtein;
 use super::TermDictionary;
-use fst::map::{Stream, StreamBuilder};
-use fst::{IntoStreamer, Streamer};
+use fst::automaton::AlwaysMatch;
+use fst::map::{Stream, StreamBuilder, StreamWithState};
+use fst::{Automaton, IntoStreamer, Streamer};
 use postings::TermInfo;
 use termdict::TermOrdinal;
 
+/// One endpoint of a term range, remembered alongside the `StreamBuilder`
+/// so it can be re-applied if the builder is later rebuilt for a
+/// different automaton (see `TermStreamerBuilder::automaton`).
+#[derive(Clone)]
+enum RangeBound {
+    Included(Vec<u8>),
+    Excluded(Vec<u8>),
+}
+
+/// The lower and upper endpoints of a term range set via `ge`/`gt`/`le`/`lt`.
+#[derive(Clone, Default)]
+struct StreamBounds {
+    lower: Option<RangeBound>,
+    upper: Option<RangeBound>,
+}
+
+impl StreamBounds {
+    /// Re-applies the remembered bounds onto a freshly created
+    /// `StreamBuilder`, e.g. one built for a different automaton.
+    ///
+    /// The input and output share an explicit lifetime `'b`, independent
+    /// of `&self`: under `&self` elision both would otherwise be tied to
+    /// the borrow of `StreamBounds`, forcing the returned `StreamBuilder`
+    /// to outlive only as long as that borrow rather than the FST it
+    /// actually points into.
+    fn apply<'b, A: Automaton>(&self, mut stream_builder: StreamBuilder<'b, A>) -> StreamBuilder<'b, A> {
+        stream_builder = match &self.lower {
+            Some(RangeBound::Included(bound)) => stream_builder.ge(bound),
+            Some(RangeBound::Excluded(bound)) => stream_builder.gt(bound),
+            None => stream_builder,
+        };
+        stream_builder = match &self.upper {
+            Some(RangeBound::Included(bound)) => stream_builder.le(bound),
+            Some(RangeBound::Excluded(bound)) => stream_builder.lt(bound),
+            None => stream_builder,
+        };
+        stream_builder
+    }
+}
+
 /// `TermStreamerBuilder` is an helper object used to define
 /// a range of terms that should be streamed.
-pub struct TermStreamerBuilder<'a> {
+pub struct TermStreamerBuilder<'a, A = AlwaysMatch>
+where
+    A: Automaton,
+{
     fst_map: &'a TermDictionary,
-    stream_builder: StreamBuilder<'a>,
+    stream_builder: StreamBuilder<'a, A>,
+    bounds: StreamBounds,
 }
 
-impl<'a> TermStreamerBuilder<'a> {
-    pub(crate) fn new(fst_map: &'a TermDictionary, stream_builder: StreamBuilder<'a>) -> Self {
+impl<'a> TermStreamerBuilder<'a, AlwaysMatch> {
+    pub(crate) fn new(fst_map: &'a TermDictionary, stream_builder: StreamBuilder<'a, AlwaysMatch>) -> Self {
         TermStreamerBuilder {
             fst_map,
             stream_builder,
+            bounds: StreamBounds::default(),
+        }
+    }
+
+    /// Restricts the stream to the terms accepted by the given `automaton`,
+    /// replacing the default `AlwaysMatch` automaton.
+    ///
+    /// Any `ge`/`gt`/`le`/`lt` bound already applied on `self` (the only
+    /// bounds reachable before `.automaton()`, since it can only be called
+    /// on the `AlwaysMatch` builder) is carried over onto the new
+    /// automaton's builder, so e.g. `.ge("a").le("z").regex(...)` keeps
+    /// its range rather than silently reverting to the whole dictionary.
+    ///
+    /// This is the same automaton-intersection capability exposed by the
+    /// underlying `fst::Map`: terms that the automaton rejects are never
+    /// visited, so whole subtrees of the FST can be skipped during the walk.
+    pub fn automaton<A: Automaton>(self, automaton: A) -> TermStreamerBuilder<'a, A> {
+        let stream_builder = self.bounds.apply(self.fst_map.fst_map().search(automaton));
+        TermStreamerBuilder {
+            fst_map: self.fst_map,
+            stream_builder,
+            bounds: self.bounds,
         }
     }
 
+    /// Restricts the stream to the terms matching the given regular
+    /// expression.
+    pub fn regex(self, regex: &str) -> Result<TermStreamerBuilder<'a, ::fst::Regex>, ::fst::Error> {
+        let automaton = ::fst::Regex::new(regex)?;
+        Ok(self.automaton(automaton))
+    }
+
+    /// Restricts the stream to the terms within `max_distance` edits of
+    /// `query`, using a Levenshtein automaton so that terms outside the
+    /// distance bound are never visited.
+    pub fn fuzzy(self, query: &str, max_distance: u8) -> TermStreamerBuilder<'a, Levenshtein> {
+        self.automaton(Levenshtein::new(query, max_distance))
+    }
+}
+
+impl<'a, A> TermStreamerBuilder<'a, A>
+where
+    A: Automaton,
+{
     /// Limit the range to terms greater or equal to the bound
     pub fn ge<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.bounds.lower = Some(RangeBound::Included(bound.as_ref().to_vec()));
         self.stream_builder = self.stream_builder.ge(bound);
         self
     }
 
     /// Limit the range to terms strictly greater than the bound
     pub fn gt<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.bounds.lower = Some(RangeBound::Excluded(bound.as_ref().to_vec()));
         self.stream_builder = self.stream_builder.gt(bound);
         self
     }
 
     /// Limit the range to terms lesser or equal to the bound
     pub fn le<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.bounds.upper = Some(RangeBound::Included(bound.as_ref().to_vec()));
         self.stream_builder = self.stream_builder.le(bound);
         self
     }
 
     /// Limit the range to terms lesser or equal to the bound
     pub fn lt<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.bounds.upper = Some(RangeBound::Excluded(bound.as_ref().to_vec()));
         self.stream_builder = self.stream_builder.lt(bound);
         self
     }
 
     /// Creates the stream corresponding to the range
     /// of terms defined using the `TermStreamerBuilder`.
-    pub fn into_stream(self) -> TermStreamer<'a> {
+    pub fn into_stream(self) -> TermStreamer<'a, A> {
         TermStreamer {
             fst_map: self.fst_map,
             stream: self.stream_builder.into_stream(),
             term_ord: 0u64,
             current_key: Vec::with_capacity(100),
             current_value: TermInfo::default(),
+            started: false,
+            exhausted: false,
+        }
+    }
+
+    /// Converts this builder into a `TermStreamerWithStateBuilder`, whose
+    /// stream also exposes the automaton's accept state for each term.
+    ///
+    /// For a Levenshtein automaton, that state is a `LevenshteinState`
+    /// whose `.distance()` gives the edit distance of the matched term,
+    /// which a fuzzy query scorer can use to rank closer matches higher.
+    pub fn with_state(self) -> TermStreamerWithStateBuilder<'a, A>
+    where
+        A::State: Clone,
+    {
+        TermStreamerWithStateBuilder {
+            fst_map: self.fst_map,
+            stream_builder: self.stream_builder,
         }
     }
 }
 
 /// `TermStreamer` acts as a cursor over a range of terms of a segment.
 /// Terms are guaranteed to be sorted.
-pub struct TermStreamer<'a> {
+pub struct TermStreamer<'a, A = AlwaysMatch>
+where
+    A: Automaton,
+{
     fst_map: &'a TermDictionary,
-    stream: Stream<'a>,
+    stream: Stream<'a, A>,
     term_ord: TermOrdinal,
     current_key: Vec<u8>,
     current_value: TermInfo,
+    started: bool,
+    exhausted: bool,
 }
 
-impl<'a> TermStreamer<'a> {
+impl<'a, A> TermStreamer<'a, A>
+where
+    A: Automaton,
+{
     /// Advance position the stream on the next item.
     /// Before the first call to `.advance()`, the stream
     /// is an unitialized state.
@@ -76,8 +193,10 @@ impl<'a> TermStreamer<'a> {
             self.current_key.extend_from_slice(term);
             self.term_ord = term_ord;
             self.current_value = self.fst_map.term_info_from_ord(term_ord);
+            self.started = true;
             true
         } else {
+            self.exhausted = true;
             false
         }
     }
@@ -125,4 +244,327 @@ impl<'a> TermStreamer<'a> {
             None
         }
     }
+
+    /// Advances the cursor to the first term greater than or equal to
+    /// `target`, resolving its `TermOrdinal`, key and `TermInfo` in one
+    /// operation.
+    ///
+    /// `seek` only ever moves forward: if the cursor already sits on a
+    /// term `>= target` (including the term the last `.advance()`/`.seek()`
+    /// landed on), it is a no-op and that same term is returned. This
+    /// matters for the block-join and phrase intersection "skip ahead to
+    /// candidate" use case, where re-seeking to the current candidate must
+    /// return the current candidate, not the one after it.
+    ///
+    /// Returns `false`, leaving the cursor exhausted, if no such term
+    /// exists. Once the stream is exhausted, every subsequent `seek` also
+    /// returns `false`: `current_key` still holds the last term visited,
+    /// but there is nothing left at or beyond it to seek to.
+    pub fn seek(&mut self, target: &[u8]) -> bool {
+        if self.exhausted {
+            return false;
+        }
+        if self.started && self.current_key.as_slice() >= target {
+            return true;
+        }
+        while self.advance() {
+            if self.current_key.as_slice() >= target {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Owned-item adapter over a `TermStreamer`, produced by
+/// `TermStreamer::into_iter()` / `IntoIterator`.
+///
+/// The borrow-returning `TermStreamer::next(&mut self) -> Option<(&[u8],
+/// &TermInfo)>` cursor method cannot satisfy `Iterator` directly (and
+/// implementing `Iterator` on `TermStreamer` itself would have its `next`
+/// shadowed by that inherent `next`, since inherent methods win method resolution over
+/// trait methods of the same name). This wrapper instead clones the key
+/// and copies the `TermInfo` per item, so term ranges can be driven
+/// through the standard `Iterator` combinators (`filter`, `map`,
+/// `take_while`, ...) while the zero-copy cursor API on `TermStreamer`
+/// stays untouched for hot paths.
+pub struct TermStreamerIter<'a, A = AlwaysMatch>
+where
+    A: Automaton,
+{
+    streamer: TermStreamer<'a, A>,
+}
+
+impl<'a, A> Iterator for TermStreamerIter<'a, A>
+where
+    A: Automaton,
+{
+    type Item = (Vec<u8>, TermInfo);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.streamer.advance() {
+            Some((
+                self.streamer.current_key.clone(),
+                self.streamer.current_value.clone(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, A> IntoIterator for TermStreamer<'a, A>
+where
+    A: Automaton,
+{
+    type Item = (Vec<u8>, TermInfo);
+    type IntoIter = TermStreamerIter<'a, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        TermStreamerIter { streamer: self }
+    }
+}
+
+/// `TermStreamerWithStateBuilder` mirrors `TermStreamerBuilder`, but produces
+/// a `TermStreamerWithState` that also yields the automaton's accept state
+/// for each streamed term.
+pub struct TermStreamerWithStateBuilder<'a, A = AlwaysMatch>
+where
+    A: Automaton,
+    A::State: Clone,
+{
+    fst_map: &'a TermDictionary,
+    stream_builder: StreamBuilder<'a, A>,
+}
+
+impl<'a, A> TermStreamerWithStateBuilder<'a, A>
+where
+    A: Automaton,
+    A::State: Clone,
+{
+    /// Limit the range to terms greater or equal to the bound
+    pub fn ge<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.stream_builder = self.stream_builder.ge(bound);
+        self
+    }
+
+    /// Limit the range to terms strictly greater than the bound
+    pub fn gt<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.stream_builder = self.stream_builder.gt(bound);
+        self
+    }
+
+    /// Limit the range to terms lesser or equal to the bound
+    pub fn le<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.stream_builder = self.stream_builder.le(bound);
+        self
+    }
+
+    /// Limit the range to terms strictly lesser than the bound
+    pub fn lt<T: AsRef<[u8]>>(mut self, bound: T) -> Self {
+        self.stream_builder = self.stream_builder.lt(bound);
+        self
+    }
+
+    /// Creates the stream corresponding to the range
+    /// of terms defined using the `TermStreamerWithStateBuilder`.
+    pub fn into_stream(self) -> TermStreamerWithState<'a, A> {
+        TermStreamerWithState {
+            fst_map: self.fst_map,
+            stream: self.stream_builder.into_stream_with_state(),
+            term_ord: 0u64,
+            current_key: Vec::with_capacity(100),
+            current_value: TermInfo::default(),
+            current_state: None,
+        }
+    }
+}
+
+/// `TermStreamerWithState` acts as a cursor over a range of terms of a
+/// segment, like `TermStreamer`, but additionally exposes the automaton's
+/// accept state reached by the current term.
+pub struct TermStreamerWithState<'a, A = AlwaysMatch>
+where
+    A: Automaton,
+    A::State: Clone,
+{
+    fst_map: &'a TermDictionary,
+    stream: StreamWithState<'a, A>,
+    term_ord: TermOrdinal,
+    current_key: Vec<u8>,
+    current_value: TermInfo,
+    current_state: Option<A::State>,
+}
+
+impl<'a, A> TermStreamerWithState<'a, A>
+where
+    A: Automaton,
+    A::State: Clone,
+{
+    /// Advance position the stream on the next item.
+    /// Before the first call to `.advance()`, the stream
+    /// is an unitialized state.
+    pub fn advance(&mut self) -> bool {
+        if let Some((term, term_ord, state)) = self.stream.next() {
+            self.current_key.clear();
+            self.current_key.extend_from_slice(term);
+            self.term_ord = term_ord;
+            self.current_value = self.fst_map.term_info_from_ord(term_ord);
+            self.current_state = Some(state);
+            true
+        } else {
+            self.current_state = None;
+            false
+        }
+    }
+
+    /// Returns the `TermOrdinal` of the given term.
+    ///
+    /// May panic if the called as `.advance()` as never
+    /// been called before.
+    pub fn term_ord(&self) -> TermOrdinal {
+        self.term_ord
+    }
+
+    /// Accesses the current key.
+    pub fn key(&self) -> &[u8] {
+        &self.current_key
+    }
+
+    /// Accesses the current value.
+    pub fn value(&self) -> &TermInfo {
+        &self.current_value
+    }
+
+    /// Accesses the automaton's accept state for the current term.
+    ///
+    /// For a `Levenshtein` automaton, call `.distance()` on the returned
+    /// `LevenshteinState` to get the matched term's edit distance.
+    ///
+    /// Returns `None` before the first call to `.advance()`, or once the
+    /// end of the stream has been reached.
+    pub fn state(&self) -> Option<&A::State> {
+        self.current_state.as_ref()
+    }
+
+    /// Return the next `(key, value)` pair.
+    pub fn next(&mut self) -> Option<(&[u8], &TermInfo)> {
+        if self.advance() {
+            Some((self.key(), self.value()))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TermDictionary;
+    use postings::TermInfo;
+
+    fn test_dictionary() -> TermDictionary {
+        TermDictionary::for_test(&[
+            ("aa", TermInfo::default()),
+            ("ab", TermInfo::default()),
+            ("bb", TermInfo::default()),
+            ("cc", TermInfo::default()),
+        ])
+    }
+
+    #[test]
+    fn test_seek_lands_on_exact_match() {
+        let dict = test_dictionary();
+        let mut stream = dict.stream();
+        assert!(stream.seek(b"bb"));
+        assert_eq!(stream.key(), b"bb");
+    }
+
+    #[test]
+    fn test_seek_lands_on_first_greater_term() {
+        let dict = test_dictionary();
+        let mut stream = dict.stream();
+        // "ba" is between "ab" and "bb": seek should land on "bb".
+        assert!(stream.seek(b"ba"));
+        assert_eq!(stream.key(), b"bb");
+    }
+
+    #[test]
+    fn test_seek_past_the_end_fails() {
+        let dict = test_dictionary();
+        let mut stream = dict.stream();
+        assert!(!stream.seek(b"zz"));
+    }
+
+    #[test]
+    fn test_reseeking_to_current_term_is_a_no_op() {
+        let dict = test_dictionary();
+        let mut stream = dict.stream();
+        assert!(stream.seek(b"bb"));
+        assert_eq!(stream.term_ord(), 2);
+        // Seeking again to a target at or before the current term must
+        // return the current term, not skip past it.
+        assert!(stream.seek(b"bb"));
+        assert_eq!(stream.key(), b"bb");
+        assert_eq!(stream.term_ord(), 2);
+        assert!(stream.seek(b"aa"));
+        assert_eq!(stream.key(), b"bb");
+    }
+
+    #[test]
+    fn test_seek_on_exhausted_stream_returns_false() {
+        let dict = test_dictionary();
+        let mut stream = dict.stream();
+        assert!(!stream.seek(b"zz"));
+        // The cursor is exhausted: even a target at or before the last
+        // term visited must not resurrect a match.
+        assert!(!stream.seek(b"aa"));
+    }
+
+    #[test]
+    fn test_into_iter_yields_same_keys_as_the_cursor() {
+        let dict = test_dictionary();
+
+        let mut cursor_keys = Vec::new();
+        let mut cursor = dict.stream();
+        while cursor.advance() {
+            cursor_keys.push(cursor.key().to_vec());
+        }
+
+        let iter_keys: Vec<Vec<u8>> = dict.stream().into_iter().map(|(key, _)| key).collect();
+
+        assert_eq!(cursor_keys, iter_keys);
+    }
+
+    #[test]
+    fn test_into_iter_supports_standard_combinators() {
+        let dict = test_dictionary();
+        let kept: Vec<Vec<u8>> = dict
+            .stream()
+            .into_iter()
+            .map(|(key, _)| key)
+            .filter(|key| key.starts_with(b"a"))
+            .collect();
+        assert_eq!(kept, vec![b"aa".to_vec(), b"ab".to_vec()]);
+    }
+
+    #[test]
+    fn test_fuzzy_with_state_exposes_edit_distance() {
+        let dict = TermDictionary::for_test(&[
+            ("cat", TermInfo::default()),
+            ("cats", TermInfo::default()),
+            ("dog", TermInfo::default()),
+        ]);
+        let mut stream = dict.range().fuzzy("cat", 1).with_state().into_stream();
+        let mut seen = Vec::new();
+        while stream.advance() {
+            let distance = stream.state().and_then(|state| state.distance());
+            seen.push((stream.key().to_vec(), distance));
+        }
+        // "dog" is more than 1 edit away from "cat" and must be pruned by
+        // the automaton rather than merely filtered after the fact.
+        assert_eq!(
+            seen,
+            vec![(b"cat".to_vec(), Some(0)), (b"cats".to_vec(), Some(1))]
+        );
+    }
 }